@@ -0,0 +1,86 @@
+//! A simple growable bit set, used to track which indexes in a slab have
+//! been flagged for a future poll.
+
+const BITS: usize = std::mem::size_of::<usize>() * 8;
+
+/// A growable collection of bits, addressable by index.
+#[derive(Debug, Default)]
+pub struct BitSet {
+    blocks: Vec<usize>,
+    cap: usize,
+}
+
+impl BitSet {
+    /// Construct a new, empty bit set.
+    pub fn new() -> Self {
+        Self {
+            blocks: Vec::new(),
+            cap: 0,
+        }
+    }
+
+    /// The number of bits this set can currently hold without reallocating.
+    pub fn capacity(&self) -> usize {
+        self.cap
+    }
+
+    /// Reserve capacity for at least `cap` bits.
+    pub fn reserve(&mut self, cap: usize) {
+        if cap <= self.cap {
+            return;
+        }
+
+        let blocks = cap.div_ceil(BITS);
+        self.blocks.resize(blocks, 0);
+        self.cap = blocks * BITS;
+    }
+
+    /// Set the given index, returning `true` if it wasn't already set.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `index` is out of bounds. Callers are expected to
+    /// [reserve][BitSet::reserve] first.
+    pub fn set(&mut self, index: usize) -> bool {
+        let block = index / BITS;
+        let mask = 1usize << (index % BITS);
+        let old = self.blocks[block];
+        self.blocks[block] |= mask;
+        old & mask == 0
+    }
+
+    /// Test if the given index is set.
+    pub fn contains(&self, index: usize) -> bool {
+        match self.blocks.get(index / BITS) {
+            Some(block) => block & (1usize << (index % BITS)) != 0,
+            None => false,
+        }
+    }
+
+    /// Clear every bit, without shrinking capacity.
+    pub fn clear(&mut self) {
+        for block in &mut self.blocks {
+            *block = 0;
+        }
+    }
+
+    /// Drain all set indexes out of the bit set, clearing them as they are
+    /// yielded.
+    pub fn drain(&mut self) -> std::vec::IntoIter<usize> {
+        let mut out = Vec::new();
+
+        for (block_index, block) in self.blocks.iter_mut().enumerate() {
+            let mut bits = *block;
+
+            while bits != 0 {
+                let bit = bits.trailing_zeros() as usize;
+                out.push(block_index * BITS + bit);
+                bits &= bits - 1;
+            }
+
+            *block = 0;
+        }
+
+        out.into_iter()
+    }
+}
@@ -0,0 +1,324 @@
+//! A combinator flattening a stream of streams into a single, unordered
+//! stream of their merged items.
+//!
+//! [FlattenUnordered] spawns every inner stream it receives from the base
+//! stream into a [Streamed], so all of them are driven concurrently with
+//! the same fairness guarantees as the rest of this crate. To avoid
+//! spuriously polling the base stream and the inner set when only one of
+//! them actually has anything new to do, both are driven through a shared
+//! `AtomicU8` poll-state machine instead of two independent wakers.
+
+use crate::Streamed;
+use crate::waker::SharedWaker;
+use futures_core::Stream;
+use std::mem;
+use std::pin::Pin;
+use std::sync::Arc;
+use std::sync::atomic::{AtomicU8, Ordering};
+use std::task::{Context, Poll, RawWaker, RawWakerVTable, Waker};
+
+/// The inner set has items to poll.
+const NEED_TO_POLL_INNER_STREAMS: u8 = 0b00_0001;
+/// The base stream has items to poll.
+const NEED_TO_POLL_STREAM: u8 = 0b00_0010;
+/// [FlattenUnordered::poll_next] is currently running; wakers should only
+/// flag their bit and let it notice on its own.
+const POLLING: u8 = 0b00_0100;
+/// A wake from the base stream is currently being forwarded to the
+/// parent, guarding against redundant wake-ups from repeated wakes.
+const WAKING_STREAM: u8 = 0b00_1000;
+/// Same as `WAKING_STREAM`, but for the inner set.
+const WAKING_INNER_STREAMS: u8 = 0b01_0000;
+
+/// A stream that flattens a stream of streams, polling every inner stream
+/// concurrently and yielding their items as soon as they are ready,
+/// without preserving any particular order.
+///
+/// Constructed by [flatten_unordered].
+pub struct FlattenUnordered<St>
+where
+    St: Stream,
+    St::Item: Stream,
+{
+    stream: Pin<Box<St>>,
+    stream_done: bool,
+    inner: Streamed<St::Item>,
+    limit: Option<usize>,
+    state: Arc<AtomicU8>,
+    parent: Arc<SharedWaker>,
+}
+
+impl<St> Unpin for FlattenUnordered<St>
+where
+    St: Stream,
+    St::Item: Stream,
+{
+}
+
+/// Flatten a stream of streams into a single, unordered stream of their
+/// merged items.
+///
+/// If `limit` is set, at most that many inner streams are polled
+/// concurrently: once the limit is reached, the base stream is not
+/// polled for new inner streams until one of the existing ones finishes.
+pub fn flatten_unordered<St>(stream: St, limit: Option<usize>) -> FlattenUnordered<St>
+where
+    St: Stream,
+    St::Item: Stream,
+{
+    FlattenUnordered {
+        stream: Box::pin(stream),
+        stream_done: false,
+        inner: Streamed::new(),
+        limit,
+        // Poll both sides at least once on the very first call.
+        state: Arc::new(AtomicU8::new(
+            NEED_TO_POLL_STREAM | NEED_TO_POLL_INNER_STREAMS,
+        )),
+        parent: Arc::new(SharedWaker::new()),
+    }
+}
+
+impl<St> Stream for FlattenUnordered<St>
+where
+    St: Stream,
+    St::Item: Stream,
+{
+    type Item = <St::Item as Stream>::Item;
+
+    fn poll_next(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        let this = Pin::into_inner(self);
+
+        this.parent.swap(cx.waker().clone());
+
+        loop {
+            let prev = this.state.fetch_and(
+                !(NEED_TO_POLL_STREAM | NEED_TO_POLL_INNER_STREAMS),
+                Ordering::AcqRel,
+            );
+            this.state.fetch_or(POLLING, Ordering::AcqRel);
+
+            let need_stream = prev & NEED_TO_POLL_STREAM != 0;
+            let need_inner = prev & NEED_TO_POLL_INNER_STREAMS != 0;
+            let mut pushed = false;
+
+            if need_stream && !this.stream_done {
+                let at_capacity = match this.limit {
+                    Some(limit) => this.inner.len() >= limit,
+                    None => false,
+                };
+
+                if !at_capacity {
+                    let waker = proxy_waker(
+                        &this.state,
+                        &this.parent,
+                        NEED_TO_POLL_STREAM,
+                        WAKING_STREAM,
+                    );
+                    let mut stream_cx = Context::from_waker(&waker);
+
+                    match this.stream.as_mut().poll_next(&mut stream_cx) {
+                        Poll::Ready(Some(inner)) => {
+                            this.inner.push(inner);
+                            pushed = true;
+                            this.state.fetch_or(NEED_TO_POLL_STREAM, Ordering::AcqRel);
+                        }
+                        Poll::Ready(None) => this.stream_done = true,
+                        Poll::Pending => {}
+                    }
+                }
+            }
+
+            if need_inner || pushed {
+                let before = this.inner.len();
+
+                let waker = proxy_waker(
+                    &this.state,
+                    &this.parent,
+                    NEED_TO_POLL_INNER_STREAMS,
+                    WAKING_INNER_STREAMS,
+                );
+                let mut inner_cx = Context::from_waker(&waker);
+                let polled = Pin::new(&mut this.inner).poll_next(&mut inner_cx);
+
+                if this.inner.len() < before {
+                    // An inner stream just finished and was dropped from the
+                    // slab, freeing up a slot under `limit`: make sure the
+                    // base stream gets a chance to fill it in again. This is
+                    // the only reliable place to notice that, since a
+                    // single-item inner stream is typically discovered to be
+                    // done on a *later*, otherwise-`Pending` poll than the
+                    // one that yielded its last item.
+                    this.state.fetch_or(NEED_TO_POLL_STREAM, Ordering::AcqRel);
+                }
+
+                if let Poll::Ready(Some(item)) = polled {
+                    let lost = this.state.fetch_and(!POLLING, Ordering::AcqRel);
+                    if lost & (NEED_TO_POLL_STREAM | NEED_TO_POLL_INNER_STREAMS) != 0 {
+                        cx.waker().wake_by_ref();
+                    }
+
+                    return Poll::Ready(Some(item));
+                }
+            }
+
+            if this.stream_done && this.inner.is_empty() {
+                this.state.fetch_and(!POLLING, Ordering::AcqRel);
+                return Poll::Ready(None);
+            }
+
+            // `fetch_and(!POLLING)` only ever clears `POLLING` itself, so
+            // any `NEED_*` bit flagged by a racing waker while we were
+            // polling is still set in `state` right now: no need to set
+            // it again, just notice it and go around once more.
+            let lost = this.state.fetch_and(!POLLING, Ordering::AcqRel)
+                & (NEED_TO_POLL_STREAM | NEED_TO_POLL_INNER_STREAMS);
+
+            if lost != 0 {
+                continue;
+            }
+
+            return Poll::Pending;
+        }
+    }
+}
+
+struct Proxy {
+    state: Arc<AtomicU8>,
+    parent: Arc<SharedWaker>,
+    bit: u8,
+    waking_bit: u8,
+}
+
+fn wake_proxy(proxy: &Proxy) {
+    proxy.state.fetch_or(proxy.bit, Ordering::AcqRel);
+
+    if proxy.state.load(Ordering::Acquire) & POLLING != 0 {
+        // The owner is (or is about to be) already polling and will
+        // observe the bit we just set; no need to forward the wake.
+        return;
+    }
+
+    // Claim the right to forward this particular kind of wake, so that a
+    // burst of wakes from the same source doesn't all hit the parent
+    // waker.
+    let already_waking =
+        proxy.state.fetch_or(proxy.waking_bit, Ordering::AcqRel) & proxy.waking_bit != 0;
+
+    if already_waking {
+        return;
+    }
+
+    proxy.parent.wake();
+    proxy.state.fetch_and(!proxy.waking_bit, Ordering::AcqRel);
+}
+
+unsafe fn clone(data: *const ()) -> RawWaker {
+    let proxy = unsafe { Arc::from_raw(data as *const Proxy) };
+    let cloned = proxy.clone();
+    mem::forget(proxy);
+    raw_waker(cloned)
+}
+
+unsafe fn wake(data: *const ()) {
+    let proxy = unsafe { Arc::from_raw(data as *const Proxy) };
+    wake_proxy(&proxy);
+}
+
+unsafe fn wake_by_ref(data: *const ()) {
+    let proxy = unsafe { Arc::from_raw(data as *const Proxy) };
+    wake_proxy(&proxy);
+    mem::forget(proxy);
+}
+
+unsafe fn drop_raw(data: *const ()) {
+    drop(unsafe { Arc::from_raw(data as *const Proxy) });
+}
+
+static VTABLE: RawWakerVTable = RawWakerVTable::new(clone, wake, wake_by_ref, drop_raw);
+
+fn raw_waker(proxy: Arc<Proxy>) -> RawWaker {
+    RawWaker::new(Arc::into_raw(proxy) as *const (), &VTABLE)
+}
+
+fn proxy_waker(state: &Arc<AtomicU8>, parent: &Arc<SharedWaker>, bit: u8, waking_bit: u8) -> Waker {
+    let proxy = Arc::new(Proxy {
+        state: state.clone(),
+        parent: parent.clone(),
+        bit,
+        waking_bit,
+    });
+
+    unsafe { Waker::from_raw(raw_waker(proxy)) }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::flatten_unordered;
+    use crate::test_util::{Counted, Delayed};
+    use futures::executor::block_on;
+    use futures::stream::StreamExt;
+
+    #[test]
+    fn flattens_every_item_from_every_inner_stream() {
+        let outer = Counted::new([Counted::new([1, 2]), Counted::new([3, 4])]);
+        let mut flattened = flatten_unordered(outer, None);
+
+        let mut items = Vec::new();
+        while let Some(item) = block_on(flattened.next()) {
+            items.push(item);
+        }
+
+        items.sort_unstable();
+        assert_eq!(items, vec![1, 2, 3, 4]);
+    }
+
+    #[test]
+    fn respects_the_concurrency_limit() {
+        let outer = Counted::new([Counted::new([1]), Counted::new([2]), Counted::new([3])]);
+        let mut flattened = flatten_unordered(outer, Some(1));
+
+        let mut items = Vec::new();
+        while let Some(item) = block_on(flattened.next()) {
+            items.push(item);
+        }
+
+        items.sort_unstable();
+        assert_eq!(items, vec![1, 2, 3]);
+    }
+
+    #[test]
+    fn ends_once_the_base_stream_and_every_inner_stream_are_done() {
+        let outer = Counted::new(Vec::<Counted<i32>>::new());
+        let mut flattened = flatten_unordered(outer, None);
+        assert_eq!(block_on(flattened.next()), None);
+    }
+
+    #[test]
+    fn drives_inner_streams_through_pending_polls() {
+        let outer = Counted::new([Delayed::new(3, [1, 2]), Delayed::new(1, [3])]);
+        let mut flattened = flatten_unordered(outer, None);
+
+        let mut items = Vec::new();
+        while let Some(item) = block_on(flattened.next()) {
+            items.push(item);
+        }
+
+        items.sort_unstable();
+        assert_eq!(items, vec![1, 2, 3]);
+    }
+
+    #[test]
+    fn drives_the_base_stream_through_pending_polls() {
+        let outer = Delayed::new(3, [Counted::new([1, 2]), Counted::new([3])]);
+        let mut flattened = flatten_unordered(outer, None);
+
+        let mut items = Vec::new();
+        while let Some(item) = block_on(flattened.next()) {
+            items.push(item);
+        }
+
+        items.sort_unstable();
+        assert_eq!(items, vec![1, 2, 3]);
+    }
+}
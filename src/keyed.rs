@@ -0,0 +1,299 @@
+//! A container for an unordered collection of [Future]s, addressable by a
+//! caller-chosen key.
+//!
+//! [Keyed] is a sibling of [Unordered][crate::Unordered] built on the same
+//! [PinSlab] + wake set engine, but keeps a `HashMap<K, usize>` from key to
+//! slab index (and the reverse mapping, so a completed or removed slot can
+//! forget its key) so that individual futures can be addressed, replaced,
+//! or cancelled by key instead of only ever completing on their own.
+
+use crate::pin_slab::PinSlab;
+use crate::wake_set::WakeSet;
+use crate::{Shared, waker};
+use futures_core::Stream;
+use std::{
+    collections::{HashMap, VecDeque},
+    future::Future,
+    hash::Hash,
+    iter, mem,
+    pin::Pin,
+    ptr,
+    sync::Arc,
+    task::{Context, Poll},
+};
+
+/// A container for an unordered collection of [Future]s, each addressable
+/// by a key of type `K`.
+///
+/// Unlike [Unordered][crate::Unordered], pushing a future returns no
+/// index: instead the caller picks the key up front with
+/// [insert][Keyed::insert], and can later look up, replace or cancel that
+/// specific future through [get_mut][Keyed::get_mut],
+/// [remove][Keyed::remove] and [contains_key][Keyed::contains_key]. The
+/// stream yields `(K, F::Output)` pairs instead of bare outputs.
+pub struct Keyed<K, F>
+where
+    F: Future,
+{
+    // Indexes that needs to be polled after they have been added.
+    pollable: Vec<usize>,
+    // Slab of futures being polled.
+    slab: PinSlab<F>,
+    // The largest index inserted into the slab so far.
+    max_index: usize,
+    // Key -> slab index.
+    keys: HashMap<K, usize>,
+    // Slab index -> key, so a completed or removed slot can be forgotten
+    // from `keys` without the caller having to hand the key back.
+    indexes: Vec<Option<K>>,
+    shared: Arc<Shared>,
+    wake_alternate: *mut WakeSet,
+    results: VecDeque<(K, F::Output)>,
+}
+
+unsafe impl<K, F> Send for Keyed<K, F> where F: Future {}
+unsafe impl<K, F> Sync for Keyed<K, F> where F: Future {}
+
+impl<K, F> Unpin for Keyed<K, F> where F: Future {}
+
+impl<K, F> Default for Keyed<K, F>
+where
+    K: Eq + Hash,
+    F: Future,
+{
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<K, F> Keyed<K, F>
+where
+    K: Eq + Hash,
+    F: Future,
+{
+    /// Construct a new, empty [Keyed].
+    pub fn new() -> Self {
+        let alternate = WakeSet::new();
+        alternate.lock_write();
+
+        Self {
+            pollable: Vec::with_capacity(16),
+            slab: PinSlab::new(),
+            max_index: 0,
+            keys: HashMap::new(),
+            indexes: Vec::new(),
+            shared: Arc::new(Shared::new()),
+            wake_alternate: Box::into_raw(Box::new(alternate)),
+            results: VecDeque::new(),
+        }
+    }
+
+    /// Test if the collection of futures is empty.
+    pub fn is_empty(&self) -> bool {
+        self.slab.is_empty()
+    }
+
+    /// The number of futures currently held by the collection.
+    pub fn len(&self) -> usize {
+        self.keys.len()
+    }
+
+    /// Test if the given key is currently associated with a future.
+    pub fn contains_key(&self, key: &K) -> bool {
+        self.keys.contains_key(key)
+    }
+
+    /// Insert `future` under `key`, replacing and returning any future
+    /// that was previously registered under it.
+    ///
+    /// The new future is guaranteed to be polled, but there is no
+    /// guarantee in which order this will happen relative to other
+    /// futures in the collection.
+    pub fn insert(&mut self, key: K, future: F) -> Option<F>
+    where
+        K: Clone,
+    {
+        let previous = self.remove_future(&key);
+
+        let index = self.slab.insert(future);
+        self.max_index = usize::max(self.max_index, index);
+
+        if self.indexes.len() <= index {
+            self.indexes.resize_with(index + 1, || None);
+        }
+
+        self.indexes[index] = Some(key.clone());
+        self.keys.insert(key, index);
+        self.pollable.push(index);
+
+        previous
+    }
+
+    /// Access the future associated with `key` mutably, if it is still
+    /// held by the collection.
+    pub fn get_mut(&mut self, key: &K) -> Option<Pin<&mut F>> {
+        let index = *self.keys.get(key)?;
+        self.slab.get_pin_mut(index)
+    }
+
+    /// Remove and return the future associated with `key`, if any.
+    ///
+    /// The caller is responsible for dropping the returned future; no
+    /// output is produced for a future removed this way.
+    pub fn remove(&mut self, key: &K) -> Option<F> {
+        self.remove_future(key)
+    }
+
+    fn remove_future(&mut self, key: &K) -> Option<F> {
+        let index = self.keys.remove(key)?;
+        self.indexes[index] = None;
+        self.slab.try_remove(index)
+    }
+}
+
+impl<K, F> Drop for Keyed<K, F>
+where
+    F: Future,
+{
+    fn drop(&mut self) {
+        // Safety: see `Unordered`'s `Drop` impl; the same invariant holds
+        // here since we manage `wake_alternate` identically.
+        unsafe {
+            WakeSet::drop_raw(self.wake_alternate);
+        }
+    }
+}
+
+impl<K, F> Stream for Keyed<K, F>
+where
+    K: Clone + Eq + Hash,
+    F: Future,
+{
+    type Item = (K, F::Output);
+
+    fn poll_next(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        let Self {
+            ref mut pollable,
+            ref mut results,
+            ref mut slab,
+            ref mut indexes,
+            ref mut keys,
+            ref shared,
+            ref mut wake_alternate,
+            max_index,
+            ..
+        } = *self.as_mut();
+
+        if let Some(value) = results.pop_front() {
+            cx.waker().wake_by_ref();
+            return Poll::Ready(Some(value));
+        }
+
+        if slab.is_empty() {
+            return Poll::Ready(None);
+        }
+
+        if !shared.waker.is_woken_by(cx.waker()) {
+            shared.waker.swap(cx.waker().clone());
+        }
+
+        let wake_last = {
+            unsafe {
+                {
+                    let set = (**wake_alternate).as_local_mut();
+
+                    if set.capacity() <= max_index {
+                        set.reserve(max_index + 1);
+                    }
+                }
+
+                (**wake_alternate).unlock_write();
+
+                let next = mem::replace(wake_alternate, ptr::null_mut());
+                *wake_alternate = shared.wake_set.swap(next);
+
+                (**wake_alternate).lock_write();
+                (**wake_alternate).as_local_mut()
+            }
+        };
+
+        let indexes_to_poll = iter::from_fn(|| pollable.pop()).chain(wake_last.drain());
+
+        for index in indexes_to_poll {
+            let fut = match slab.get_pin_mut(index) {
+                Some(fut) => fut,
+                None => continue,
+            };
+
+            let result = waker::poll_with_ref(shared, index, move |cx| fut.poll(cx));
+
+            if let Poll::Ready(result) = result {
+                slab.remove(index);
+
+                if let Some(key) = indexes[index].take() {
+                    keys.remove(&key);
+                    results.push_back((key, result));
+                }
+            }
+        }
+
+        if let Some(value) = results.pop_front() {
+            cx.waker().wake_by_ref();
+            return Poll::Ready(Some(value));
+        }
+
+        Poll::Pending
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Keyed;
+    use crate::test_util::Countdown;
+    use futures::executor::block_on;
+    use futures::stream::StreamExt;
+
+    #[test]
+    fn insert_and_complete_yields_key_and_output() {
+        let mut keyed = Keyed::new();
+        keyed.insert("a", Countdown::new(2, 1));
+
+        let (key, value) = block_on(keyed.next()).unwrap();
+        assert_eq!(key, "a");
+        assert_eq!(value, 1);
+        assert!(keyed.is_empty());
+    }
+
+    #[test]
+    fn insert_replaces_and_returns_previous_future() {
+        let mut keyed = Keyed::new();
+        keyed.insert("a", Countdown::new(10, 1));
+        let previous = keyed.insert("a", Countdown::new(0, 2));
+        assert!(previous.is_some());
+        assert_eq!(keyed.len(), 1);
+
+        let (key, value) = block_on(keyed.next()).unwrap();
+        assert_eq!(key, "a");
+        assert_eq!(value, 2);
+    }
+
+    #[test]
+    fn remove_cancels_without_producing_output() {
+        let mut keyed: Keyed<&str, Countdown<i32>> = Keyed::new();
+        keyed.insert("a", Countdown::new(10, 1));
+        assert!(keyed.contains_key(&"a"));
+
+        assert!(keyed.remove(&"a").is_some());
+        assert!(!keyed.contains_key(&"a"));
+        assert!(keyed.is_empty());
+    }
+
+    #[test]
+    fn get_mut_only_finds_live_keys() {
+        let mut keyed: Keyed<&str, Countdown<i32>> = Keyed::new();
+        keyed.insert("a", Countdown::new(10, 1));
+
+        assert!(keyed.get_mut(&"a").is_some());
+        assert!(keyed.get_mut(&"b").is_none());
+    }
+}
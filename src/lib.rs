@@ -50,7 +50,7 @@
 //! }
 //! ```
 
-use self::pin_slab::PinSlab;
+use self::pin_slab::{IterPinMut as SlabIterPinMut, IterPinRef as SlabIterPinRef, PinSlab};
 use self::wake_set::{SharedWakeSet, WakeSet};
 use self::waker::SharedWaker;
 use futures_core::Stream;
@@ -65,9 +65,19 @@ use std::{
 };
 
 pub use self::bit_set::BitSet;
+pub use self::flatten_unordered::{FlattenUnordered, flatten_unordered};
+pub use self::keyed::Keyed;
+pub use self::ordered::Ordered;
+pub use self::streamed::Streamed;
 
 mod bit_set;
+mod flatten_unordered;
+mod keyed;
+mod ordered;
 mod pin_slab;
+mod streamed;
+#[cfg(test)]
+mod test_util;
 mod wake_set;
 mod waker;
 
@@ -89,6 +99,18 @@ impl Shared {
     }
 }
 
+/// An opaque handle to a future previously pushed onto an [Unordered],
+/// returned by [push][Unordered::push] and accepted by
+/// [remove][Unordered::remove].
+///
+/// A `Key` carries a generation alongside the slab index it names, so
+/// that it keeps referring to *that* `push`, and not whatever future
+/// later comes to occupy the same slot once the original has completed
+/// and been reclaimed. [remove][Unordered::remove] always returns `false`
+/// for a `Key` whose generation has been superseded this way.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct Key(usize, usize);
+
 /// A container for an unordered collection of [Future]s.
 pub struct Unordered<F>
 where
@@ -109,6 +131,12 @@ where
     // Alternate wake set, used for growing the existing set when futures are
     // added. This is then swapped out with the active set to receive polls.
     wake_alternate: *mut WakeSet,
+    // Slab index -> current generation, bumped every time a slot is
+    // occupied so that a `Key` handed out for a since-reclaimed slot can
+    // be told apart from one handed out for whatever future occupies it
+    // now. Never rolled back, including by `clear`, so generations stay
+    // unique across the lifetime of the collection.
+    generations: Vec<usize>,
     // Pending outgoing results. Uses a queue to avoid interrupting polling.
     results: VecDeque<F::Output>,
 }
@@ -118,6 +146,15 @@ unsafe impl<F> Sync for Unordered<F> where F: Future {}
 
 impl<F> Unpin for Unordered<F> where F: Future {}
 
+impl<F> Default for Unordered<F>
+where
+    F: Future,
+{
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
 impl<F> Unordered<F>
 where
     F: Future,
@@ -133,6 +170,7 @@ where
             max_index: 0,
             shared: Arc::new(Shared::new()),
             wake_alternate: Box::into_raw(Box::new(alternate)),
+            generations: Vec::new(),
             results: VecDeque::new(),
         }
     }
@@ -142,14 +180,116 @@ where
         self.slab.is_empty()
     }
 
-    /// Add the given future to the [Unordered] stream.
+    /// The number of futures currently held by the collection.
+    pub fn len(&self) -> usize {
+        self.slab.len()
+    }
+
+    /// Add the given future to the [Unordered] stream, returning a [Key]
+    /// that can later be used to [remove][Unordered::remove] it.
     ///
     /// Newly added futures are guaranteed to be polled, but there is no
-    /// guarantee in which order this will happen.
-    pub fn push(&mut self, future: F) {
+    /// guarantee in which order this will happen. The returned `Key` is
+    /// only valid until this particular future completes on its own;
+    /// using it afterwards is safe but will not affect whatever future
+    /// has since taken its slot (see [Key]).
+    pub fn push(&mut self, future: F) -> Key {
         let index = self.slab.insert(future);
         self.max_index = usize::max(self.max_index, index);
         self.pollable.push(index);
+
+        if self.generations.len() <= index {
+            self.generations.resize(index + 1, 0);
+        }
+
+        self.generations[index] += 1;
+        Key(index, self.generations[index])
+    }
+
+    /// Remove the future associated with `key`, returning `true` if it
+    /// was still held by the collection.
+    ///
+    /// Returns `false` without disturbing anything if `key` names a slot
+    /// that has since been reclaimed by a later [push][Unordered::push],
+    /// rather than removing whatever future now lives there.
+    ///
+    /// The removed future is dropped in place; no output is produced for
+    /// a future removed this way.
+    pub fn remove(&mut self, key: Key) -> bool {
+        if self.generations.get(key.0) != Some(&key.1) {
+            return false;
+        }
+
+        self.slab.remove(key.0)
+    }
+
+    /// Clear the collection, dropping every future currently held by it.
+    pub fn clear(&mut self) {
+        self.slab = PinSlab::new();
+        self.pollable.clear();
+        self.results.clear();
+        self.max_index = 0;
+
+        // Deliberately not reset: `generations` must keep counting up
+        // across a `clear` so that a `Key` obtained before it can never
+        // collide with one handed out for the same slot afterwards.
+
+        // Swap in a fresh, empty active wake set so that any waker still
+        // racing to mark an index from before the clear observes a set
+        // with nothing worth waking, rather than entries referring to
+        // futures that no longer exist.
+        let fresh = Box::into_raw(Box::new(WakeSet::new()));
+        let previous = self.shared.wake_set.swap(fresh);
+
+        unsafe {
+            // Safety: `previous` was the active set until the swap above.
+            // A racing waker might still be transiently touching it
+            // through its own lock/unlock in `WakeSet::mark`, so we must
+            // claim exclusive access the same way a poll would before
+            // reusing it, rather than freeing it outright.
+            (*previous).lock_write();
+            (*previous).as_local_mut().clear();
+
+            // Safety: `wake_alternate` is never shared with child
+            // wakers, so we are free to drop it and replace it with the
+            // set we just claimed above.
+            WakeSet::drop_raw(self.wake_alternate);
+        }
+
+        self.wake_alternate = previous;
+    }
+
+    /// Iterate over the futures currently held, pinned.
+    pub fn iter_pin_ref(self: Pin<&Self>) -> IterPinRef<'_, F> {
+        IterPinRef {
+            iter: Pin::get_ref(self).slab.iter_pin_ref(),
+        }
+    }
+
+    /// Iterate mutably over the futures currently held, pinned.
+    pub fn iter_pin_mut(self: Pin<&mut Self>) -> IterPinMut<'_, F> {
+        IterPinMut {
+            iter: Pin::get_mut(self).slab.iter_pin_mut(),
+        }
+    }
+}
+
+impl<F> Unordered<F>
+where
+    F: Future + Unpin,
+{
+    /// Iterate over the futures currently held.
+    pub fn iter(&self) -> Iter<'_, F> {
+        Iter {
+            iter: self.slab.iter_pin_ref(),
+        }
+    }
+
+    /// Iterate mutably over the futures currently held.
+    pub fn iter_mut(&mut self) -> IterMut<'_, F> {
+        IterMut {
+            iter: self.slab.iter_pin_mut(),
+        }
     }
 }
 
@@ -271,3 +411,159 @@ where
         Poll::Pending
     }
 }
+
+/// An iterator over pinned, shared references to the futures held by an
+/// [Unordered].
+pub struct IterPinRef<'a, F> {
+    iter: SlabIterPinRef<'a, F>,
+}
+
+impl<'a, F> Iterator for IterPinRef<'a, F>
+where
+    F: Future,
+{
+    type Item = Pin<&'a F>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.iter.next()
+    }
+}
+
+/// An iterator over pinned, mutable references to the futures held by an
+/// [Unordered].
+pub struct IterPinMut<'a, F> {
+    iter: SlabIterPinMut<'a, F>,
+}
+
+impl<'a, F> Iterator for IterPinMut<'a, F>
+where
+    F: Future,
+{
+    type Item = Pin<&'a mut F>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.iter.next()
+    }
+}
+
+/// An iterator over shared references to the futures held by an
+/// [Unordered].
+pub struct Iter<'a, F> {
+    iter: SlabIterPinRef<'a, F>,
+}
+
+impl<'a, F> Iterator for Iter<'a, F>
+where
+    F: Future + Unpin,
+{
+    type Item = &'a F;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        Some(Pin::into_inner(self.iter.next()?))
+    }
+}
+
+/// An iterator over mutable references to the futures held by an
+/// [Unordered].
+pub struct IterMut<'a, F> {
+    iter: SlabIterPinMut<'a, F>,
+}
+
+impl<'a, F> Iterator for IterMut<'a, F>
+where
+    F: Future + Unpin,
+{
+    type Item = &'a mut F;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        Some(Pin::into_inner(self.iter.next()?))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Unordered;
+    use crate::test_util::Countdown;
+    use futures::executor::block_on;
+    use futures::stream::StreamExt;
+    use std::pin::Pin;
+
+    #[test]
+    fn iter_and_iter_mut_visit_every_pushed_future() {
+        let mut unordered: Unordered<Countdown<i32>> = Unordered::new();
+        unordered.push(Countdown::new(5, 1));
+        unordered.push(Countdown::new(5, 2));
+
+        assert_eq!(unordered.iter().count(), 2);
+        assert_eq!(unordered.iter_mut().count(), 2);
+    }
+
+    #[test]
+    fn iter_pin_ref_and_iter_pin_mut_visit_every_pushed_future() {
+        let mut unordered: Unordered<Countdown<i32>> = Unordered::new();
+        unordered.push(Countdown::new(5, 1));
+        unordered.push(Countdown::new(5, 2));
+
+        assert_eq!(Pin::new(&unordered).iter_pin_ref().count(), 2);
+        assert_eq!(Pin::new(&mut unordered).iter_pin_mut().count(), 2);
+    }
+
+    #[test]
+    fn iter_mut_grants_access_to_drive_a_future_to_readiness() {
+        let mut unordered: Unordered<Countdown<i32>> = Unordered::new();
+        unordered.push(Countdown::new(5, 1));
+
+        for countdown in unordered.iter_mut() {
+            countdown.finish_now();
+        }
+
+        let value = block_on(unordered.next()).unwrap();
+        assert_eq!(value, 1);
+    }
+
+    #[test]
+    fn len_and_clear() {
+        let mut unordered: Unordered<Countdown<i32>> = Unordered::new();
+        unordered.push(Countdown::new(5, 1));
+        unordered.push(Countdown::new(5, 2));
+        assert_eq!(unordered.len(), 2);
+
+        unordered.clear();
+        assert_eq!(unordered.len(), 0);
+        assert!(unordered.is_empty());
+    }
+
+    #[test]
+    fn remove_by_key_cancels_the_future() {
+        let mut unordered: Unordered<Countdown<i32>> = Unordered::new();
+        let key = unordered.push(Countdown::new(10, 1));
+        assert_eq!(unordered.len(), 1);
+
+        assert!(unordered.remove(key));
+        assert_eq!(unordered.len(), 0);
+
+        // The slot was already vacant, so removing the same key twice is
+        // a no-op rather than disturbing anything.
+        assert!(!unordered.remove(key));
+    }
+
+    #[test]
+    fn a_stale_key_does_not_remove_whatever_reused_its_slot() {
+        let mut unordered: Unordered<Countdown<i32>> = Unordered::new();
+        let key_a = unordered.push(Countdown::new(0, 1));
+
+        // Let `a` complete and its slot be reclaimed.
+        assert_eq!(block_on(unordered.next()), Some(1));
+
+        // `b` reuses the same slab slot that `a` just vacated.
+        let key_b = unordered.push(Countdown::new(10, 2));
+        assert_eq!(key_a.0, key_b.0);
+        assert_ne!(key_a, key_b);
+
+        // The caller's old key for `a` must not reach into `b`'s slot.
+        assert!(!unordered.remove(key_a));
+        assert_eq!(unordered.len(), 1);
+
+        assert_eq!(block_on(unordered.next()), Some(2));
+    }
+}
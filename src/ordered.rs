@@ -0,0 +1,281 @@
+//! A container for an unordered collection of [Future]s that nonetheless
+//! yields their outputs in the order the futures were pushed.
+//!
+//! [Ordered] drives every future concurrently through the same
+//! [PinSlab] + wake set engine as [Unordered][crate::Unordered], but
+//! tags each pushed future with a monotonically increasing sequence
+//! number. Outputs that complete out of order are buffered in a
+//! [BinaryHeap] keyed by that sequence number, and are only moved into
+//! the outgoing queue once the next expected sequence number is among
+//! them.
+
+use crate::pin_slab::PinSlab;
+use crate::wake_set::WakeSet;
+use crate::{Shared, waker};
+use futures_core::Stream;
+use std::{
+    cmp::{Ordering as CmpOrdering, Reverse},
+    collections::{BinaryHeap, VecDeque},
+    future::Future,
+    iter, mem,
+    pin::Pin,
+    ptr,
+    sync::Arc,
+    task::{Context, Poll},
+};
+
+/// A completed output, ordered solely by the sequence number it was
+/// pushed with.
+struct Buffered<T> {
+    seq: usize,
+    value: T,
+}
+
+impl<T> PartialEq for Buffered<T> {
+    fn eq(&self, other: &Self) -> bool {
+        self.seq == other.seq
+    }
+}
+
+impl<T> Eq for Buffered<T> {}
+
+impl<T> PartialOrd for Buffered<T> {
+    fn partial_cmp(&self, other: &Self) -> Option<CmpOrdering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl<T> Ord for Buffered<T> {
+    fn cmp(&self, other: &Self) -> CmpOrdering {
+        self.seq.cmp(&other.seq)
+    }
+}
+
+/// A container for an unordered collection of [Future]s that yields
+/// their outputs in the order the futures were pushed in, rather than
+/// the order in which they complete.
+pub struct Ordered<F>
+where
+    F: Future,
+{
+    // Indexes that needs to be polled after they have been added.
+    pollable: Vec<usize>,
+    // Slab of futures being polled.
+    slab: PinSlab<F>,
+    // The largest index inserted into the slab so far.
+    max_index: usize,
+    // Slab index -> sequence number, so a completed future can recover
+    // the sequence number it was pushed under.
+    seqs: Vec<Option<usize>>,
+    // Sequence number to assign to the next pushed future.
+    next_seq: usize,
+    // Sequence number of the next output to yield.
+    next_to_yield: usize,
+    // Outputs that completed ahead of their turn, keyed by sequence
+    // number so the lowest one is always available first.
+    buffered: BinaryHeap<Reverse<Buffered<F::Output>>>,
+    shared: Arc<Shared>,
+    wake_alternate: *mut WakeSet,
+    // Outputs ready to be yielded, in order.
+    results: VecDeque<F::Output>,
+}
+
+unsafe impl<F> Send for Ordered<F> where F: Future {}
+unsafe impl<F> Sync for Ordered<F> where F: Future {}
+
+impl<F> Unpin for Ordered<F> where F: Future {}
+
+impl<F> Default for Ordered<F>
+where
+    F: Future,
+{
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<F> Ordered<F>
+where
+    F: Future,
+{
+    /// Construct a new, empty [Ordered].
+    pub fn new() -> Self {
+        let alternate = WakeSet::new();
+        alternate.lock_write();
+
+        Self {
+            pollable: Vec::with_capacity(16),
+            slab: PinSlab::new(),
+            max_index: 0,
+            seqs: Vec::new(),
+            next_seq: 0,
+            next_to_yield: 0,
+            buffered: BinaryHeap::new(),
+            shared: Arc::new(Shared::new()),
+            wake_alternate: Box::into_raw(Box::new(alternate)),
+            results: VecDeque::new(),
+        }
+    }
+
+    /// Test if the collection of futures is empty.
+    pub fn is_empty(&self) -> bool {
+        self.slab.is_empty()
+    }
+
+    /// Add the given future to the [Ordered] stream.
+    ///
+    /// The future is guaranteed to be polled concurrently with every
+    /// other future already in the collection, but its output will only
+    /// be yielded once every future pushed before it has yielded theirs.
+    pub fn push(&mut self, future: F) {
+        let seq = self.next_seq;
+        self.next_seq += 1;
+
+        let index = self.slab.insert(future);
+        self.max_index = usize::max(self.max_index, index);
+
+        if self.seqs.len() <= index {
+            self.seqs.resize_with(index + 1, || None);
+        }
+
+        self.seqs[index] = Some(seq);
+        self.pollable.push(index);
+    }
+}
+
+impl<F> Drop for Ordered<F>
+where
+    F: Future,
+{
+    fn drop(&mut self) {
+        // Safety: see `Unordered`'s `Drop` impl; the same invariant holds
+        // here since we manage `wake_alternate` identically.
+        unsafe {
+            WakeSet::drop_raw(self.wake_alternate);
+        }
+    }
+}
+
+impl<F> Stream for Ordered<F>
+where
+    F: Future,
+{
+    type Item = F::Output;
+
+    fn poll_next(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        let Self {
+            ref mut pollable,
+            ref mut results,
+            ref mut slab,
+            ref mut seqs,
+            ref mut buffered,
+            ref mut next_to_yield,
+            ref shared,
+            ref mut wake_alternate,
+            max_index,
+            ..
+        } = *self.as_mut();
+
+        if let Some(value) = results.pop_front() {
+            cx.waker().wake_by_ref();
+            return Poll::Ready(Some(value));
+        }
+
+        if slab.is_empty() {
+            return Poll::Ready(None);
+        }
+
+        if !shared.waker.is_woken_by(cx.waker()) {
+            shared.waker.swap(cx.waker().clone());
+        }
+
+        let wake_last = {
+            unsafe {
+                {
+                    let set = (**wake_alternate).as_local_mut();
+
+                    if set.capacity() <= max_index {
+                        set.reserve(max_index + 1);
+                    }
+                }
+
+                (**wake_alternate).unlock_write();
+
+                let next = mem::replace(wake_alternate, ptr::null_mut());
+                *wake_alternate = shared.wake_set.swap(next);
+
+                (**wake_alternate).lock_write();
+                (**wake_alternate).as_local_mut()
+            }
+        };
+
+        let indexes = iter::from_fn(|| pollable.pop()).chain(wake_last.drain());
+
+        for index in indexes {
+            let fut = match slab.get_pin_mut(index) {
+                Some(fut) => fut,
+                None => continue,
+            };
+
+            let result = waker::poll_with_ref(shared, index, move |cx| fut.poll(cx));
+
+            if let Poll::Ready(value) = result {
+                slab.remove(index);
+                let seq = seqs[index]
+                    .take()
+                    .expect("polled index without a sequence number");
+                buffered.push(Reverse(Buffered { seq, value }));
+            }
+        }
+
+        while let Some(Reverse(buffered_value)) = buffered.peek() {
+            if buffered_value.seq != *next_to_yield {
+                break;
+            }
+
+            let Reverse(buffered_value) = buffered.pop().expect("just peeked");
+            results.push_back(buffered_value.value);
+            *next_to_yield += 1;
+        }
+
+        if let Some(value) = results.pop_front() {
+            cx.waker().wake_by_ref();
+            return Poll::Ready(Some(value));
+        }
+
+        Poll::Pending
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Ordered;
+    use crate::test_util::Countdown;
+    use futures::executor::block_on;
+    use futures::stream::StreamExt;
+
+    #[test]
+    fn yields_outputs_in_push_order_even_when_they_complete_out_of_order() {
+        let mut ordered = Ordered::new();
+        ordered.push(Countdown::new(5, 1));
+        ordered.push(Countdown::new(1, 2));
+        ordered.push(Countdown::new(3, 3));
+
+        let mut items = Vec::new();
+        while let Some(item) = block_on(ordered.next()) {
+            items.push(item);
+        }
+
+        assert_eq!(items, vec![1, 2, 3]);
+    }
+
+    #[test]
+    fn a_single_future_is_yielded_on_its_own() {
+        let mut ordered = Ordered::new();
+        ordered.push(Countdown::new(2, "a"));
+
+        assert_eq!(block_on(ordered.next()), Some("a"));
+        assert_eq!(block_on(ordered.next()), None);
+        assert!(ordered.is_empty());
+    }
+}
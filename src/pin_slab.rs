@@ -0,0 +1,238 @@
+//! A slab of pinned, fixed-size memory regions.
+//!
+//! Unlike a plain `Vec`, growing a [PinSlab] never moves an entry that has
+//! already been inserted: growth only ever appends a new, independently
+//! heap-allocated region. This makes it possible to store `!Unpin` values
+//! (such as futures) and keep handing out stable `Pin<&mut T>` references
+//! to them even while more entries are added.
+
+use std::mem;
+use std::pin::Pin;
+
+const FIRST_CAPACITY: usize = 16;
+
+enum Entry<T> {
+    Vacant,
+    Occupied(T),
+}
+
+/// A slab of pinned values, indexed by a stable `usize` key.
+pub(crate) struct PinSlab<T> {
+    // Regions double in capacity each time, so region `n` holds
+    // `FIRST_CAPACITY << n` entries.
+    regions: Vec<Box<[Entry<T>]>>,
+    // Total number of occupied entries.
+    occupied: usize,
+    // The first index past every region allocated so far.
+    capacity: usize,
+    // Indexes that have been vacated and can be reused.
+    free: Vec<usize>,
+}
+
+impl<T> PinSlab<T> {
+    /// Construct a new, empty pin slab.
+    pub(crate) fn new() -> Self {
+        Self {
+            regions: Vec::new(),
+            occupied: 0,
+            capacity: 0,
+            free: Vec::new(),
+        }
+    }
+
+    /// Test if the slab holds no values.
+    pub(crate) fn is_empty(&self) -> bool {
+        self.occupied == 0
+    }
+
+    /// The number of values currently held by the slab.
+    pub(crate) fn len(&self) -> usize {
+        self.occupied
+    }
+
+    /// Insert a value into the slab, returning the index it was inserted
+    /// at.
+    pub(crate) fn insert(&mut self, value: T) -> usize {
+        let index = match self.free.pop() {
+            Some(index) => index,
+            None => {
+                let base = self.capacity;
+                self.grow();
+
+                // Growing only ever allocates one region at a time, so
+                // `base` is the index we occupy now; every other slot in
+                // the freshly allocated region is free for later inserts.
+                self.free.extend((base + 1..self.capacity).rev());
+                base
+            }
+        };
+
+        let (region, offset) = self.locate(index);
+        self.regions[region][offset] = Entry::Occupied(value);
+        self.occupied += 1;
+        index
+    }
+
+    /// Remove the value at `index`, returning `true` if a value was
+    /// removed.
+    pub(crate) fn remove(&mut self, index: usize) -> bool {
+        self.try_remove(index).is_some()
+    }
+
+    /// Remove and return the value at `index`, if any.
+    pub(crate) fn try_remove(&mut self, index: usize) -> Option<T> {
+        let (region, offset) = self.try_locate(index)?;
+
+        match mem::replace(&mut self.regions[region][offset], Entry::Vacant) {
+            Entry::Occupied(value) => {
+                self.occupied -= 1;
+                self.free.push(index);
+                Some(value)
+            }
+            Entry::Vacant => None,
+        }
+    }
+
+    /// Access the value at `index` as a pinned, mutable reference.
+    ///
+    /// This is sound without requiring `Pin<&mut Self>`: entries live in
+    /// independently heap-allocated regions that are never moved or
+    /// reused while occupied, regardless of how the slab itself is moved.
+    pub(crate) fn get_pin_mut(&mut self, index: usize) -> Option<Pin<&mut T>> {
+        let (region, offset) = self.try_locate(index)?;
+
+        match &mut self.regions[region][offset] {
+            Entry::Occupied(value) => Some(unsafe { Pin::new_unchecked(value) }),
+            Entry::Vacant => None,
+        }
+    }
+
+    /// Iterate over pinned, mutable references to every occupied value in
+    /// the slab, in ascending index order.
+    pub(crate) fn iter_pin_mut(&mut self) -> IterPinMut<'_, T> {
+        IterPinMut {
+            regions: self.regions.iter_mut(),
+            entries: [].iter_mut(),
+        }
+    }
+
+    /// Iterate over pinned, shared references to every occupied value in
+    /// the slab, in ascending index order.
+    pub(crate) fn iter_pin_ref(&self) -> IterPinRef<'_, T> {
+        IterPinRef {
+            regions: self.regions.iter(),
+            entries: [].iter(),
+        }
+    }
+
+    fn locate(&self, index: usize) -> (usize, usize) {
+        self.try_locate(index).expect("index out of bounds")
+    }
+
+    fn try_locate(&self, index: usize) -> Option<(usize, usize)> {
+        let mut base = 0;
+        let mut cap = FIRST_CAPACITY;
+
+        for region in 0..self.regions.len() {
+            if index < base + cap {
+                return Some((region, index - base));
+            }
+
+            base += cap;
+            cap *= 2;
+        }
+
+        None
+    }
+
+    /// Allocate a new region, twice the size of the previous one.
+    fn grow(&mut self) {
+        let cap = FIRST_CAPACITY << self.regions.len();
+        let mut region = Vec::with_capacity(cap);
+        region.resize_with(cap, || Entry::Vacant);
+        self.regions.push(region.into_boxed_slice());
+        self.capacity += cap;
+    }
+}
+
+/// An iterator over pinned, mutable references to the occupied values of
+/// a [PinSlab].
+pub(crate) struct IterPinMut<'a, T> {
+    regions: std::slice::IterMut<'a, Box<[Entry<T>]>>,
+    entries: std::slice::IterMut<'a, Entry<T>>,
+}
+
+impl<'a, T> Iterator for IterPinMut<'a, T> {
+    type Item = Pin<&'a mut T>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        loop {
+            match self.entries.next() {
+                Some(Entry::Occupied(value)) => return Some(unsafe { Pin::new_unchecked(value) }),
+                Some(Entry::Vacant) => continue,
+                None => {
+                    self.entries = self.regions.next()?.iter_mut();
+                }
+            }
+        }
+    }
+}
+
+/// An iterator over pinned, shared references to the occupied values of a
+/// [PinSlab].
+pub(crate) struct IterPinRef<'a, T> {
+    regions: std::slice::Iter<'a, Box<[Entry<T>]>>,
+    entries: std::slice::Iter<'a, Entry<T>>,
+}
+
+impl<'a, T> Iterator for IterPinRef<'a, T> {
+    type Item = Pin<&'a T>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        loop {
+            match self.entries.next() {
+                Some(Entry::Occupied(value)) => return Some(unsafe { Pin::new_unchecked(value) }),
+                Some(Entry::Vacant) => continue,
+                None => {
+                    self.entries = self.regions.next()?.iter();
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::PinSlab;
+
+    #[test]
+    fn insert_reuses_every_slot_in_a_freshly_grown_region() {
+        let mut slab = PinSlab::new();
+
+        let first_region: Vec<usize> = (0..16).map(|i| slab.insert(i)).collect();
+        assert_eq!(first_region, (0..16).collect::<Vec<_>>());
+
+        // Growing the slab to fit a 17th value must not waste the rest
+        // of the newly allocated region: the next 31 inserts should
+        // land in it before a third region is ever allocated.
+        let second_region: Vec<usize> = (16..48).map(|i| slab.insert(i)).collect();
+        assert_eq!(second_region, (16..48).collect::<Vec<_>>());
+    }
+
+    #[test]
+    fn removed_slots_are_reused() {
+        let mut slab = PinSlab::new();
+
+        let a = slab.insert(1);
+        let b = slab.insert(2);
+        assert!(slab.remove(a));
+
+        let c = slab.insert(3);
+        assert_eq!(c, a);
+        assert_eq!(slab.len(), 2);
+
+        assert_eq!(slab.try_remove(b), Some(2));
+        assert_eq!(slab.try_remove(c), Some(3));
+        assert!(slab.is_empty());
+    }
+}
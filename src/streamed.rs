@@ -0,0 +1,251 @@
+//! A container for an unordered collection of [Stream]s.
+//!
+//! [Streamed] is a sibling of [Unordered][crate::Unordered] built on the
+//! same [PinSlab] + wake set engine. The difference is in what happens
+//! once a child is ready: a one-shot future is always removed from the
+//! slab on completion, but a stream should keep being driven after it
+//! yields an item, and should only be removed once it yields `None`.
+
+use crate::pin_slab::PinSlab;
+use crate::wake_set::WakeSet;
+use crate::{Shared, waker};
+use futures_core::Stream;
+use std::{
+    collections::VecDeque,
+    iter, mem,
+    pin::Pin,
+    ptr,
+    sync::Arc,
+    task::{Context, Poll},
+};
+
+/// A container for an unordered collection of [Stream]s, merging the
+/// items produced by every stream it holds as they become available.
+pub struct Streamed<S>
+where
+    S: Stream,
+{
+    // Indexes that needs to be polled after they have been added.
+    pollable: Vec<usize>,
+    // Slab of streams being polled.
+    slab: PinSlab<S>,
+    // The largest index inserted into the slab so far.
+    max_index: usize,
+    shared: Arc<Shared>,
+    wake_alternate: *mut WakeSet,
+    // Pending outgoing results. Uses a queue to avoid interrupting polling.
+    results: VecDeque<S::Item>,
+}
+
+unsafe impl<S> Send for Streamed<S> where S: Stream {}
+unsafe impl<S> Sync for Streamed<S> where S: Stream {}
+
+impl<S> Unpin for Streamed<S> where S: Stream {}
+
+impl<S> Default for Streamed<S>
+where
+    S: Stream,
+{
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<S> Streamed<S>
+where
+    S: Stream,
+{
+    /// Construct a new, empty [Streamed].
+    pub fn new() -> Self {
+        let alternate = WakeSet::new();
+        alternate.lock_write();
+
+        Self {
+            pollable: Vec::with_capacity(16),
+            slab: PinSlab::new(),
+            max_index: 0,
+            shared: Arc::new(Shared::new()),
+            wake_alternate: Box::into_raw(Box::new(alternate)),
+            results: VecDeque::new(),
+        }
+    }
+
+    /// Test if the collection of streams is empty.
+    pub fn is_empty(&self) -> bool {
+        self.slab.is_empty()
+    }
+
+    /// The number of streams currently held by the collection.
+    pub fn len(&self) -> usize {
+        self.slab.len()
+    }
+
+    /// Add the given stream to the [Streamed] collection.
+    ///
+    /// Newly added streams are guaranteed to be polled, but there is no
+    /// guarantee in which order this will happen.
+    pub fn push(&mut self, stream: S) {
+        let index = self.slab.insert(stream);
+        self.max_index = usize::max(self.max_index, index);
+        self.pollable.push(index);
+    }
+}
+
+impl<S> Drop for Streamed<S>
+where
+    S: Stream,
+{
+    fn drop(&mut self) {
+        // Safety: see `Unordered`'s `Drop` impl; the same invariant holds
+        // here since we manage `wake_alternate` identically.
+        unsafe {
+            WakeSet::drop_raw(self.wake_alternate);
+        }
+    }
+}
+
+impl<S> Stream for Streamed<S>
+where
+    S: Stream,
+{
+    type Item = S::Item;
+
+    fn poll_next(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        let Self {
+            ref mut pollable,
+            ref mut results,
+            ref mut slab,
+            ref shared,
+            ref mut wake_alternate,
+            max_index,
+            ..
+        } = *self.as_mut();
+
+        if let Some(value) = results.pop_front() {
+            cx.waker().wake_by_ref();
+            return Poll::Ready(Some(value));
+        }
+
+        if slab.is_empty() {
+            return Poll::Ready(None);
+        }
+
+        if !shared.waker.is_woken_by(cx.waker()) {
+            shared.waker.swap(cx.waker().clone());
+        }
+
+        let wake_last = {
+            unsafe {
+                {
+                    let set = (**wake_alternate).as_local_mut();
+
+                    if set.capacity() <= max_index {
+                        set.reserve(max_index + 1);
+                    }
+                }
+
+                (**wake_alternate).unlock_write();
+
+                let next = mem::replace(wake_alternate, ptr::null_mut());
+                *wake_alternate = shared.wake_set.swap(next);
+
+                (**wake_alternate).lock_write();
+                (**wake_alternate).as_local_mut()
+            }
+        };
+
+        // Snapshot the indexes to poll this round. We can't poll off of
+        // `pollable` directly as we go, since a ready stream gets pushed
+        // back onto it below and we don't want to spin-poll it again
+        // before yielding control back to the executor.
+        let indexes: Vec<usize> = iter::from_fn(|| pollable.pop())
+            .chain(wake_last.drain())
+            .collect();
+
+        for index in indexes {
+            let stream = match slab.get_pin_mut(index) {
+                Some(stream) => stream,
+                None => continue,
+            };
+
+            let result = waker::poll_with_ref(shared, index, move |cx| stream.poll_next(cx));
+
+            match result {
+                Poll::Ready(Some(item)) => {
+                    // The stream produced an item but isn't necessarily
+                    // done: keep it in the slab and make sure it gets
+                    // polled again next time around.
+                    results.push_back(item);
+                    pollable.push(index);
+                }
+                Poll::Ready(None) => {
+                    let removed = slab.remove(index);
+                    debug_assert!(removed);
+                }
+                Poll::Pending => {}
+            }
+        }
+
+        if let Some(value) = results.pop_front() {
+            cx.waker().wake_by_ref();
+            return Poll::Ready(Some(value));
+        }
+
+        Poll::Pending
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Streamed;
+    use crate::test_util::Counted;
+    use futures::executor::block_on;
+    use futures::stream::StreamExt;
+
+    #[test]
+    fn drains_every_item_of_a_single_stream_in_order() {
+        let mut streamed = Streamed::new();
+        streamed.push(Counted::new([1, 2, 3]));
+
+        let mut items = Vec::new();
+        while let Some(item) = block_on(streamed.next()) {
+            items.push(item);
+        }
+
+        assert_eq!(items, vec![1, 2, 3]);
+        assert!(streamed.is_empty());
+    }
+
+    #[test]
+    fn merges_items_from_every_pushed_stream() {
+        let mut streamed = Streamed::new();
+        streamed.push(Counted::new([1, 2]));
+        streamed.push(Counted::new([3, 4]));
+        assert_eq!(streamed.len(), 2);
+
+        let mut items = Vec::new();
+        while let Some(item) = block_on(streamed.next()) {
+            items.push(item);
+        }
+
+        items.sort_unstable();
+        assert_eq!(items, vec![1, 2, 3, 4]);
+        assert!(streamed.is_empty());
+    }
+
+    #[test]
+    fn a_finished_stream_is_removed_while_others_keep_going() {
+        let mut streamed = Streamed::new();
+        streamed.push(Counted::new([1]));
+        streamed.push(Counted::new([2, 3]));
+
+        let mut items = Vec::new();
+        while let Some(item) = block_on(streamed.next()) {
+            items.push(item);
+        }
+
+        items.sort_unstable();
+        assert_eq!(items, vec![1, 2, 3]);
+        assert!(streamed.is_empty());
+    }
+}
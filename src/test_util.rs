@@ -0,0 +1,124 @@
+//! Hand-written futures and streams with controlled `Poll` sequences,
+//! shared by this crate's unit tests so each of them doesn't have to
+//! reinvent the same busy-waitable building blocks.
+#![cfg(test)]
+
+use futures_core::Stream;
+use std::collections::VecDeque;
+use std::future::Future;
+use std::pin::Pin;
+use std::task::{Context, Poll};
+
+/// A future that yields [Poll::Pending] `pending` times, waking itself
+/// each time, before resolving to `value`.
+pub(crate) struct Countdown<T> {
+    pending: usize,
+    value: Option<T>,
+}
+
+impl<T> Countdown<T> {
+    /// Construct a future that resolves to `value` after being polled
+    /// `pending + 1` times.
+    pub(crate) fn new(pending: usize, value: T) -> Self {
+        Self {
+            pending,
+            value: Some(value),
+        }
+    }
+
+    /// Make the future resolve on its very next poll.
+    pub(crate) fn finish_now(&mut self) {
+        self.pending = 0;
+    }
+}
+
+impl<T> Future for Countdown<T>
+where
+    T: Unpin,
+{
+    type Output = T;
+
+    fn poll(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        if self.pending > 0 {
+            self.pending -= 1;
+            cx.waker().wake_by_ref();
+            return Poll::Pending;
+        }
+
+        Poll::Ready(
+            self.value
+                .take()
+                .expect("Countdown polled after completion"),
+        )
+    }
+}
+
+/// A stream that yields every value in `values`, in order, waking itself
+/// in between, then ends.
+pub(crate) struct Counted<T> {
+    values: VecDeque<T>,
+}
+
+impl<T> Counted<T> {
+    pub(crate) fn new(values: impl IntoIterator<Item = T>) -> Self {
+        Self {
+            values: values.into_iter().collect(),
+        }
+    }
+}
+
+impl<T> Stream for Counted<T>
+where
+    T: Unpin,
+{
+    type Item = T;
+
+    fn poll_next(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        match self.values.pop_front() {
+            Some(value) => {
+                cx.waker().wake_by_ref();
+                Poll::Ready(Some(value))
+            }
+            None => Poll::Ready(None),
+        }
+    }
+}
+
+/// A stream that yields [Poll::Pending] `pending` times, waking itself
+/// each time, before draining `values` in order and then ending.
+pub(crate) struct Delayed<T> {
+    pending: usize,
+    values: VecDeque<T>,
+}
+
+impl<T> Delayed<T> {
+    pub(crate) fn new(pending: usize, values: impl IntoIterator<Item = T>) -> Self {
+        Self {
+            pending,
+            values: values.into_iter().collect(),
+        }
+    }
+}
+
+impl<T> Stream for Delayed<T>
+where
+    T: Unpin,
+{
+    type Item = T;
+
+    fn poll_next(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        if self.pending > 0 {
+            self.pending -= 1;
+            cx.waker().wake_by_ref();
+            return Poll::Pending;
+        }
+
+        match self.values.pop_front() {
+            Some(value) => {
+                cx.waker().wake_by_ref();
+                Poll::Ready(Some(value))
+            }
+            None => Poll::Ready(None),
+        }
+    }
+}
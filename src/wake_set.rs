@@ -0,0 +1,149 @@
+//! The wake set shared between a collection and the wakers associated with
+//! the futures it holds.
+//!
+//! A [WakeSet] is a [BitSet] guarded by a simple lock: the owning
+//! collection takes exclusive, mutable access to it while draining it
+//! during a poll, and child wakers race to flip individual bits the rest
+//! of the time. [SharedWakeSet] is the atomically swappable pointer to the
+//! currently active [WakeSet], shared between a collection and every
+//! waker it has handed out.
+
+use crate::bit_set::BitSet;
+use std::cell::UnsafeCell;
+use std::sync::atomic::{AtomicPtr, AtomicUsize, Ordering};
+
+const UNLOCKED: usize = 0;
+const LOCKED: usize = 1;
+
+/// A set of indexes that have been flagged to be polled.
+pub struct WakeSet {
+    lock: AtomicUsize,
+    set: UnsafeCell<BitSet>,
+}
+
+// Safety: access to `set` is only ever granted while `lock` is held.
+unsafe impl Send for WakeSet {}
+unsafe impl Sync for WakeSet {}
+
+impl WakeSet {
+    /// Construct a new, unlocked, empty wake set.
+    pub fn new() -> Self {
+        Self {
+            lock: AtomicUsize::new(UNLOCKED),
+            set: UnsafeCell::new(BitSet::new()),
+        }
+    }
+
+    /// Lock the set for exclusive, mutable access by the owning collection.
+    ///
+    /// Spins until any child waker that is momentarily holding the lock
+    /// to flip a bit has released it.
+    pub fn lock_write(&self) {
+        while self
+            .lock
+            .compare_exchange_weak(UNLOCKED, LOCKED, Ordering::AcqRel, Ordering::Acquire)
+            .is_err()
+        {
+            std::hint::spin_loop();
+        }
+    }
+
+    /// Unlock the set, allowing child wakers to flip bits in it again.
+    pub fn unlock_write(&self) {
+        self.lock.store(UNLOCKED, Ordering::Release);
+    }
+
+    /// Access the underlying bit set as local, mutable state.
+    ///
+    /// # Safety
+    ///
+    /// The caller must have exclusive access, which is only guaranteed
+    /// while the set is locked through [WakeSet::lock_write] and no one
+    /// else holds a reference obtained the same way.
+    #[allow(clippy::mut_from_ref)]
+    pub unsafe fn as_local_mut(&self) -> &mut BitSet {
+        unsafe { &mut *self.set.get() }
+    }
+
+    /// Flip the bit at `index`, locking momentarily to do so, and return
+    /// `true` if it was flipped.
+    ///
+    /// Returns `false` without flipping anything if the set is currently
+    /// locked, by the owning collection or by another waker.
+    fn mark(&self, index: usize) -> bool {
+        if self
+            .lock
+            .compare_exchange(UNLOCKED, LOCKED, Ordering::AcqRel, Ordering::Acquire)
+            .is_err()
+        {
+            return false;
+        }
+
+        unsafe {
+            let set = &mut *self.set.get();
+            set.reserve(index + 1);
+            set.set(index);
+        }
+
+        self.lock.store(UNLOCKED, Ordering::Release);
+        true
+    }
+
+    /// Drop a wake set previously allocated with `Box::into_raw`.
+    ///
+    /// # Safety
+    ///
+    /// The caller must uniquely own `ptr`.
+    pub unsafe fn drop_raw(ptr: *mut WakeSet) {
+        drop(unsafe { Box::from_raw(ptr) });
+    }
+}
+
+/// An atomically swappable pointer to the currently active [WakeSet].
+pub struct SharedWakeSet {
+    ptr: AtomicPtr<WakeSet>,
+}
+
+impl SharedWakeSet {
+    /// Construct a new shared wake set, pre-populated with an empty wake
+    /// set that child wakers can immediately mark into.
+    pub fn new() -> Self {
+        let set = Box::into_raw(Box::new(WakeSet::new()));
+        Self {
+            ptr: AtomicPtr::new(set),
+        }
+    }
+
+    /// Swap the currently active wake set for `next`, returning the one
+    /// that was active.
+    pub fn swap(&self, next: *mut WakeSet) -> *mut WakeSet {
+        self.ptr.swap(next, Ordering::AcqRel)
+    }
+
+    /// Mark `index` as woken in the currently active wake set.
+    ///
+    /// If the set found is locked, the active set is fetched again and
+    /// the mark retried, so the index is never dropped.
+    pub(crate) fn mark(&self, index: usize) {
+        loop {
+            let ptr = self.ptr.load(Ordering::Acquire);
+
+            // Safety: the pointee is always a valid, live `WakeSet` for as
+            // long as the `Unordered` it belongs to is alive, which is
+            // guaranteed by every waker holding an `Arc<Shared>`.
+            if unsafe { (*ptr).mark(index) } {
+                return;
+            }
+
+            std::hint::spin_loop();
+        }
+    }
+}
+
+impl Drop for SharedWakeSet {
+    fn drop(&mut self) {
+        unsafe {
+            WakeSet::drop_raw(*self.ptr.get_mut());
+        }
+    }
+}
@@ -0,0 +1,106 @@
+//! The waker handed to an individual future held by a collection, and the
+//! parent waker it wakes in turn.
+
+use crate::Shared;
+use std::mem;
+use std::sync::atomic::{AtomicU8, Ordering};
+use std::sync::{Arc, Mutex};
+use std::task::{Context, Poll, RawWaker, RawWakerVTable, Waker};
+
+/// A waker that can be atomically swapped out for another, used to hold
+/// on to the most recently observed parent waker.
+pub struct SharedWaker {
+    waker: Mutex<Option<Waker>>,
+    // Cheap pre-check so `wake` can avoid taking the lock when nothing has
+    // been registered yet.
+    registered: AtomicU8,
+}
+
+impl SharedWaker {
+    /// Construct a new, empty shared waker.
+    pub fn new() -> Self {
+        Self {
+            waker: Mutex::new(None),
+            registered: AtomicU8::new(0),
+        }
+    }
+
+    /// Swap out the currently registered waker for `waker`.
+    pub fn swap(&self, waker: Waker) {
+        *self.waker.lock().unwrap() = Some(waker);
+        self.registered.store(1, Ordering::Release);
+    }
+
+    /// Test if this shared waker is already woken by `other`.
+    pub fn is_woken_by(&self, other: &Waker) -> bool {
+        match &*self.waker.lock().unwrap() {
+            Some(waker) => waker.will_wake(other),
+            None => false,
+        }
+    }
+
+    /// Wake the registered parent waker, if any.
+    pub fn wake(&self) {
+        if self.registered.load(Ordering::Acquire) == 0 {
+            return;
+        }
+
+        if let Some(waker) = self.waker.lock().unwrap().clone() {
+            waker.wake();
+        }
+    }
+}
+
+struct Inner {
+    shared: Arc<Shared>,
+    index: usize,
+}
+
+fn wake_inner(inner: &Inner) {
+    inner.shared.wake_set.mark(inner.index);
+    inner.shared.waker.wake();
+}
+
+unsafe fn clone(data: *const ()) -> RawWaker {
+    let inner = unsafe { Arc::from_raw(data as *const Inner) };
+    let cloned = inner.clone();
+    mem::forget(inner);
+    raw_waker(cloned)
+}
+
+unsafe fn wake(data: *const ()) {
+    let inner = unsafe { Arc::from_raw(data as *const Inner) };
+    wake_inner(&inner);
+}
+
+unsafe fn wake_by_ref(data: *const ()) {
+    let inner = unsafe { Arc::from_raw(data as *const Inner) };
+    wake_inner(&inner);
+    mem::forget(inner);
+}
+
+unsafe fn drop_raw(data: *const ()) {
+    drop(unsafe { Arc::from_raw(data as *const Inner) });
+}
+
+static VTABLE: RawWakerVTable = RawWakerVTable::new(clone, wake, wake_by_ref, drop_raw);
+
+fn raw_waker(inner: Arc<Inner>) -> RawWaker {
+    RawWaker::new(Arc::into_raw(inner) as *const (), &VTABLE)
+}
+
+/// Poll `poll` with a lightweight waker that, when woken, marks `index` in
+/// `shared`'s wake set and wakes the registered parent waker.
+pub(crate) fn poll_with_ref<F, T>(shared: &Arc<Shared>, index: usize, poll: F) -> Poll<T>
+where
+    F: FnOnce(&mut Context<'_>) -> Poll<T>,
+{
+    let inner = Arc::new(Inner {
+        shared: shared.clone(),
+        index,
+    });
+
+    let waker = unsafe { Waker::from_raw(raw_waker(inner)) };
+    let mut cx = Context::from_waker(&waker);
+    poll(&mut cx)
+}